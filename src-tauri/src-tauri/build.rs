@@ -0,0 +1,15 @@
+fn main() {
+    // Re-export the host target triple so `env!("TARGET")` can name the
+    // `externalBin` sidecar (cargo only exposes TARGET to build scripts).
+    println!(
+        "cargo:rustc-env=TARGET={}",
+        std::env::var("TARGET").expect("TARGET is set by cargo for build scripts")
+    );
+
+    // Only run the Tauri build step for the desktop binary; a lib-only build
+    // (used for CI and the manifest-verifier tests) does not process
+    // tauri.conf.json and should not require the Tauri system dependencies.
+    if std::env::var_os("CARGO_FEATURE_APP").is_some() {
+        tauri_build::build();
+    }
+}
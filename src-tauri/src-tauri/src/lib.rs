@@ -0,0 +1,328 @@
+//! Platform-agnostic core for the Axiom Hive host: the sidecar error type,
+//! the signed integrity-manifest verifier, and the concurrency primitive
+//! shared by the supervisor and the session store. None of this depends on
+//! Tauri, so it builds and is unit-tested on its own; the Tauri command
+//! layer (behind the `app` feature) wires it to `AppHandle`/`Window`.
+
+// See: https://docs.rs/sha2/latest/sha2
+// See: https://docs.rs/ed25519-dalek/latest/ed25519_dalek
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+// Typed failures for every sidecar command. Serialized to a
+// `{ kind, message }` payload so the frontend can branch on the cause
+// instead of string-matching, mirroring the runtime `Error` enum pattern
+// Tauri itself exposes to JS.
+#[derive(Debug, thiserror::Error)]
+pub enum SidecarError {
+    #[error("sidecar integrity check failed")]
+    IntegrityFailed,
+    #[error("sidecar binary not found: {0}")]
+    BinaryNotFound(PathBuf),
+    #[error("failed to spawn sidecar: {0}")]
+    Spawn(#[from] std::io::Error),
+    #[error("sidecar exited with status {0}")]
+    NonZeroExit(i32),
+    #[error("sidecar run timed out")]
+    Timeout,
+    #[error("sidecar manifest error: {0}")]
+    Manifest(String),
+    #[error("sidecar session error: {0}")]
+    Session(String),
+    #[error("internal sidecar error: {0}")]
+    Internal(String),
+}
+
+impl SidecarError {
+    // Stable discriminant string handed to the frontend as `kind`.
+    fn kind(&self) -> &'static str {
+        match self {
+            SidecarError::IntegrityFailed => "integrityFailed",
+            SidecarError::BinaryNotFound(_) => "binaryNotFound",
+            SidecarError::Spawn(_) => "spawn",
+            SidecarError::NonZeroExit(_) => "nonZeroExit",
+            SidecarError::Timeout => "timeout",
+            SidecarError::Manifest(_) => "manifest",
+            SidecarError::Session(_) => "session",
+            SidecarError::Internal(_) => "internal",
+        }
+    }
+}
+
+impl serde::Serialize for SidecarError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("SidecarError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+// Embedded Ed25519 public key (32 raw bytes, hex) used to authenticate the
+// sidecar integrity manifest. Rotating the *signing* key only requires
+// replacing this constant and re-signing; the trusted hashes themselves
+// live in the shipped, signature-verified manifest and need no rebuild.
+const MANIFEST_PUBLIC_KEY_HEX: &str =
+    "fbf41640c4d9405730f67bd5104c20a6ef2a211e04a5ebd09e5bb16e6c05a8c4";
+
+// How long a supervised run may take before it is killed, and how many
+// sidecars may run at once. Callers can override the timeout per run.
+pub const DEFAULT_RUN_TIMEOUT_MS: u64 = 30_000;
+pub const MAX_CONCURRENT_SIDECARS: usize = 4;
+
+// Warm IPC sessions are capped independently of one-shot runs: a session
+// holds its permit for its entire open lifetime, so sharing one pool with
+// short runs would let a handful of idle sessions starve every run. How
+// long a caller will wait for any permit before giving up.
+pub const MAX_CONCURRENT_SESSIONS: usize = 4;
+pub const PERMIT_ACQUIRE_TIMEOUT_MS: u64 = 5_000;
+
+// Signed list of every sidecar binary and its expected SHA-256. Shipped as
+// a bundled resource (`sidecar/manifest.json`) alongside a detached
+// signature (`sidecar/manifest.json.sig`) over the exact manifest bytes.
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    // Map of target-triple filename (e.g. `main-x86_64-unknown-linux-gnu`)
+    // to its lowercase hex SHA-256.
+    binaries: HashMap<String, String>,
+}
+
+impl Manifest {
+    // Expected hash for a resolved binary's file name, if the manifest
+    // lists it.
+    pub fn expected_hash(&self, file_name: &str) -> Option<&str> {
+        self.binaries.get(file_name).map(String::as_str)
+    }
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>, String> {
+    if s.len() % 2 != 0 {
+        return Err("odd-length hex string".to_string());
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|e| e.to_string()))
+        .collect()
+}
+
+fn embedded_public_key() -> Result<VerifyingKey, SidecarError> {
+    let err = |m: &str| SidecarError::Manifest(m.to_string());
+    let key_bytes: [u8; 32] = decode_hex(MANIFEST_PUBLIC_KEY_HEX)
+        .map_err(|e| err(&e))?
+        .try_into()
+        .map_err(|_| err("public key must be 32 bytes"))?;
+    VerifyingKey::from_bytes(&key_bytes).map_err(|e| SidecarError::Manifest(e.to_string()))
+}
+
+// Verify `bytes` against `sig_hex` with `key`, and only then parse it as a
+// manifest. Factored out from the embedded-key entry point so the
+// signature path can be exercised with a test key. Rejects on any failure
+// — a bad signature must never yield a manifest.
+fn verify_manifest_with_key(
+    key: &VerifyingKey,
+    bytes: &[u8],
+    sig_hex: &str,
+) -> Result<Manifest, SidecarError> {
+    let err = |m: &str| SidecarError::Manifest(m.to_string());
+
+    let sig_bytes: [u8; 64] = decode_hex(sig_hex.trim())
+        .map_err(|e| err(&e))?
+        .try_into()
+        .map_err(|_| err("signature must be 64 bytes"))?;
+    let signature = Signature::from_bytes(&sig_bytes);
+
+    key.verify(bytes, &signature)
+        .map_err(|_| err("sidecar manifest signature is invalid"))?;
+
+    serde_json::from_slice(bytes).map_err(|e| SidecarError::Manifest(e.to_string()))
+}
+
+// Parse and signature-verify manifest bytes against the embedded public
+// key. Rejects on any failure — a missing, malformed, or mis-signed
+// manifest must never fall through to "allow".
+pub fn parse_signed_manifest(bytes: &[u8], sig_hex: &str) -> Result<Manifest, SidecarError> {
+    verify_manifest_with_key(&embedded_public_key()?, bytes, sig_hex)
+}
+
+pub fn compute_sha256<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// A minimal counting semaphore built on a `Condvar`, matching the crate's
+// std-thread style rather than pulling in an async runtime. `acquire`
+// blocks until a permit is free and returns a guard that releases it on
+// drop.
+pub struct Semaphore {
+    permits: Mutex<usize>,
+    available: Condvar,
+}
+
+// Owned permit: it holds an `Arc` to the semaphore so it can outlive the
+// acquiring call and be moved onto a background thread (e.g. held for a
+// streaming run's lifetime and released when the child exits).
+pub struct SemaphorePermit(Arc<Semaphore>);
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Self {
+        Semaphore {
+            permits: Mutex::new(permits),
+            available: Condvar::new(),
+        }
+    }
+
+    // Block until a permit is free, then take it. Prefer `acquire_timeout`
+    // on request paths so a saturated pool can't wedge a command forever.
+    pub fn acquire(self: &Arc<Self>) -> SemaphorePermit {
+        let mut permits = self.permits.lock().unwrap();
+        while *permits == 0 {
+            permits = self.available.wait(permits).unwrap();
+        }
+        *permits -= 1;
+        SemaphorePermit(self.clone())
+    }
+
+    // Like `acquire`, but give up after `timeout` and return `None` instead
+    // of blocking indefinitely when every permit is held.
+    pub fn acquire_timeout(self: &Arc<Self>, timeout: Duration) -> Option<SemaphorePermit> {
+        let permits = self.permits.lock().unwrap();
+        let (mut permits, result) = self
+            .available
+            .wait_timeout_while(permits, timeout, |p| *p == 0)
+            .unwrap();
+        if result.timed_out() || *permits == 0 {
+            return None;
+        }
+        *permits -= 1;
+        Some(SemaphorePermit(self.clone()))
+    }
+}
+
+impl Drop for SemaphorePermit {
+    fn drop(&mut self) {
+        *self.0.permits.lock().unwrap() += 1;
+        self.0.available.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn test_keypair() -> (SigningKey, VerifyingKey) {
+        let sk = SigningKey::from_bytes(&[7u8; 32]);
+        let vk = sk.verifying_key();
+        (sk, vk)
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    const MANIFEST: &[u8] =
+        br#"{"binaries":{"main-x86_64-unknown-linux-gnu":"abc123"}}"#;
+
+    #[test]
+    fn accepts_a_correctly_signed_manifest() {
+        let (sk, vk) = test_keypair();
+        let sig = hex(&sk.sign(MANIFEST).to_bytes());
+
+        let manifest = verify_manifest_with_key(&vk, MANIFEST, &sig).expect("valid manifest");
+        assert_eq!(
+            manifest.expected_hash("main-x86_64-unknown-linux-gnu"),
+            Some("abc123")
+        );
+    }
+
+    #[test]
+    fn rejects_a_tampered_manifest() {
+        let (sk, vk) = test_keypair();
+        let sig = hex(&sk.sign(MANIFEST).to_bytes());
+
+        let mut tampered = MANIFEST.to_vec();
+        *tampered.last_mut().unwrap() = b' ';
+
+        let err = verify_manifest_with_key(&vk, &tampered, &sig).unwrap_err();
+        assert!(matches!(err, SidecarError::Manifest(_)));
+    }
+
+    #[test]
+    fn rejects_a_signature_from_the_wrong_key() {
+        let (sk, _) = test_keypair();
+        let sig = hex(&sk.sign(MANIFEST).to_bytes());
+        let other = SigningKey::from_bytes(&[9u8; 32]).verifying_key();
+
+        assert!(verify_manifest_with_key(&other, MANIFEST, &sig).is_err());
+    }
+
+    #[test]
+    fn rejects_a_malformed_signature() {
+        let (_, vk) = test_keypair();
+        assert!(verify_manifest_with_key(&vk, MANIFEST, "not-hex").is_err());
+        assert!(verify_manifest_with_key(&vk, MANIFEST, "00ff").is_err());
+    }
+
+    #[test]
+    fn expected_hash_is_none_for_an_unlisted_binary() {
+        let (sk, vk) = test_keypair();
+        let sig = hex(&sk.sign(MANIFEST).to_bytes());
+        let manifest = verify_manifest_with_key(&vk, MANIFEST, &sig).unwrap();
+        assert_eq!(manifest.expected_hash("main-aarch64-apple-darwin"), None);
+    }
+
+    #[test]
+    fn embedded_public_key_decodes() {
+        embedded_public_key().expect("shipped public key is valid");
+    }
+
+    #[test]
+    fn compute_sha256_matches_known_vector() {
+        let dir = std::env::temp_dir();
+        let path = dir.join("axiom_hive_sha_test.bin");
+        fs::write(&path, b"abc").unwrap();
+        let hash = compute_sha256(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(
+            hash,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn acquire_timeout_gives_up_when_exhausted() {
+        let sem = Arc::new(Semaphore::new(1));
+        let held = sem.acquire();
+        assert!(sem
+            .acquire_timeout(Duration::from_millis(20))
+            .is_none());
+        drop(held);
+        assert!(sem
+            .acquire_timeout(Duration::from_millis(20))
+            .is_some());
+    }
+
+    #[test]
+    fn semaphore_releases_permit_on_drop() {
+        let sem = Arc::new(Semaphore::new(1));
+        {
+            let _permit = sem.acquire();
+            assert_eq!(*sem.permits.lock().unwrap(), 0);
+        }
+        assert_eq!(*sem.permits.lock().unwrap(), 1);
+    }
+}
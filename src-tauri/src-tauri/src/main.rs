@@ -4,67 +4,539 @@
     windows_subsystem = "windows"
 )]
 
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write as _};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use std::fs;
-use std::env;
-use sha2::{Digest, Sha256};
-use tauri::{Manager, Window};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use interprocess::local_socket::{LocalSocketListener, LocalSocketStream};
+use shared_child::SharedChild;
+use tauri::{AppHandle, Manager, State, Window};
+
+use axiom_hive::{
+    compute_sha256, parse_signed_manifest, Manifest, Semaphore, SemaphorePermit, SidecarError,
+    DEFAULT_RUN_TIMEOUT_MS, MAX_CONCURRENT_SESSIONS, MAX_CONCURRENT_SIDECARS,
+    PERMIT_ACQUIRE_TIMEOUT_MS,
+};
 
-// See: https://docs.rs/sha2/latest/sha2
 // See: https://docs.rs/tauri/latest/tauri
 
-const TRUSTED_HASH: &str = include_str!("trusted_sidecar.sha256");
+// Load and signature-verify the integrity manifest from the bundled
+// resources. Rejects on any failure — a missing, malformed, or mis-signed
+// manifest must never fall through to "allow".
+fn load_manifest(app: &AppHandle) -> Result<Manifest, SidecarError> {
+    let err = |m: &str| SidecarError::Manifest(m.to_string());
+
+    let manifest_path = app
+        .path_resolver()
+        .resolve_resource("sidecar/manifest.json")
+        .ok_or_else(|| err("could not resolve sidecar manifest"))?;
+    let sig_path = app
+        .path_resolver()
+        .resolve_resource("sidecar/manifest.json.sig")
+        .ok_or_else(|| err("could not resolve sidecar manifest signature"))?;
 
-fn compute_sha256<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<String> {
-    let mut file = fs::File::open(path)?;
-    let mut hasher = Sha256::new();
-    std::io::copy(&mut file, &mut hasher)?;
-    Ok(format!("{:x}", hasher.finalize()))
+    let bytes = std::fs::read(&manifest_path).map_err(|e| SidecarError::Manifest(e.to_string()))?;
+    let sig_hex =
+        std::fs::read_to_string(&sig_path).map_err(|e| SidecarError::Manifest(e.to_string()))?;
+
+    parse_signed_manifest(&bytes, &sig_hex)
 }
 
-fn verify_sidecar(sidecar_path: &str) -> bool {
-    if let Ok(hash) = compute_sha256(sidecar_path) {
-        hash.trim() == TRUSTED_HASH.trim()
+// The sidecar is shipped as a Tauri `externalBin`, so its on-disk name
+// carries the current platform target triple (e.g.
+// `main-x86_64-unknown-linux-gnu`, `main-x86_64-pc-windows-msvc.exe`).
+// The triple is baked in at build time via the `TARGET` env var exported
+// by the build script / cargo.
+const SIDECAR_TARGET_TRIPLE: &str = env!("TARGET");
+
+// Resolve the bundled sidecar binary from the app's resource directory so
+// dev and installed runs share one code path — relative paths would
+// otherwise resolve against the CWD and break once installed.
+fn sidecar_path(app: &AppHandle) -> Result<PathBuf, SidecarError> {
+    let name = if cfg!(windows) {
+        format!("sidecar/dist/main-{SIDECAR_TARGET_TRIPLE}.exe")
     } else {
-        false
+        format!("sidecar/dist/main-{SIDECAR_TARGET_TRIPLE}")
+    };
+
+    app.path_resolver()
+        .resolve_resource(&name)
+        .ok_or_else(|| SidecarError::BinaryNotFound(PathBuf::from(name)))
+}
+
+// Verify a resolved sidecar binary against the signed manifest: look up
+// the expected hash for its target-triple filename and compare against the
+// freshly computed SHA-256. Any missing entry or mismatch is a rejection.
+fn verify_sidecar(app: &AppHandle, sidecar_path: &Path) -> bool {
+    let manifest = match load_manifest(app) {
+        Ok(manifest) => manifest,
+        Err(_) => return false,
+    };
+
+    let name = match sidecar_path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name,
+        None => return false,
+    };
+
+    let expected = match manifest.expected_hash(name) {
+        Some(expected) => expected,
+        None => return false,
+    };
+
+    match compute_sha256(sidecar_path) {
+        Ok(hash) => hash.eq_ignore_ascii_case(expected.trim()),
+        Err(_) => false,
+    }
+}
+
+// Supervises sidecar runs: bounds total concurrency and holds live child
+// handles so a one-shot run can be cancelled or timed out by id.
+struct Supervisor {
+    sem: Arc<Semaphore>,
+    running: Arc<Mutex<HashMap<String, Arc<SharedChild>>>>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Supervisor {
+            sem: Arc::new(Semaphore::new(MAX_CONCURRENT_SIDECARS)),
+            running: Arc::new(Mutex::new(HashMap::new())),
+        }
     }
 }
 
+// Run a sidecar once under supervision: a concurrency permit is held for
+// the duration, the child is tracked by `id` so it can be cancelled, and
+// the run is killed if it exceeds `timeout_ms` (defaulting to
+// `DEFAULT_RUN_TIMEOUT_MS`). stdin is written on a separate thread so a
+// full pipe buffer can't deadlock against us reading stdout.
 #[tauri::command]
-fn run_sidecar(input: String) -> Result<String, String> {
-    let sidecar_path = if cfg!(windows) {
-        "sidecar/dist/main.exe"
-    } else {
-        "sidecar/dist/main"
+fn run_sidecar(
+    app: AppHandle,
+    supervisor: State<Supervisor>,
+    id: String,
+    input: String,
+    timeout_ms: Option<u64>,
+) -> Result<String, SidecarError> {
+    let sidecar_path = sidecar_path(&app)?;
+
+    if !verify_sidecar(&app, &sidecar_path) {
+        return Err(SidecarError::IntegrityFailed);
+    }
+
+    let _permit = supervisor
+        .sem
+        .acquire_timeout(Duration::from_millis(PERMIT_ACQUIRE_TIMEOUT_MS))
+        .ok_or(SidecarError::Timeout)?;
+
+    let mut command = Command::new(&sidecar_path);
+    command.stdin(Stdio::piped()).stdout(Stdio::piped());
+    let child = Arc::new(SharedChild::spawn(&mut command)?);
+
+    supervisor
+        .running
+        .lock()
+        .map_err(|e| SidecarError::Internal(e.to_string()))?
+        .insert(id.clone(), child.clone());
+
+    // Feed stdin from its own thread to avoid a write/read deadlock.
+    if let Some(mut stdin) = child.take_stdin() {
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        });
+    }
+
+    // Drain stdout concurrently; the read completes when the child exits.
+    let (out_tx, out_rx) = mpsc::channel();
+    if let Some(mut stdout) = child.take_stdout() {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stdout.read_to_end(&mut buf);
+            let _ = out_tx.send(buf);
+        });
+    }
+
+    // Wait on the child from a helper thread so we can bound it with a
+    // timeout on the main command thread.
+    let (done_tx, done_rx) = mpsc::channel();
+    let waiter = child.clone();
+    std::thread::spawn(move || {
+        let _ = done_tx.send(waiter.wait());
+    });
+
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_RUN_TIMEOUT_MS));
+    let result = match done_rx.recv_timeout(timeout) {
+        Ok(Ok(status)) if status.success() => {
+            let output = out_rx.recv().unwrap_or_default();
+            Ok(String::from_utf8_lossy(&output).to_string())
+        }
+        Ok(Ok(status)) => Err(SidecarError::NonZeroExit(status.code().unwrap_or(-1))),
+        Ok(Err(e)) => Err(SidecarError::Spawn(e)),
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            let _ = child.kill();
+            Err(SidecarError::Timeout)
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            Err(SidecarError::Internal("sidecar wait failed".to_string()))
+        }
     };
 
-    if !verify_sidecar(sidecar_path) {
-        return Err("Sidecar integrity check failed".to_string());
+    supervisor
+        .running
+        .lock()
+        .map_err(|e| SidecarError::Internal(e.to_string()))?
+        .remove(&id);
+
+    result
+}
+
+// Kill a running supervised sidecar by id. Returns an error if no run with
+// that id is currently tracked.
+#[tauri::command]
+fn cancel_sidecar(supervisor: State<Supervisor>, id: String) -> Result<(), SidecarError> {
+    let child = supervisor
+        .running
+        .lock()
+        .map_err(|e| SidecarError::Internal(e.to_string()))?
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| SidecarError::Session(format!("no running sidecar {id}")))?;
+    child.kill().map_err(SidecarError::Spawn)
+}
+
+// Monotonic id handed back to the frontend so it can correlate the
+// `sidecar://*` events for a given streaming run.
+static NEXT_RUN_ID: AtomicU64 = AtomicU64::new(1);
+
+// Stream a sidecar's stdout/stderr back to the invoking window instead of
+// buffering the whole output. Mirrors Tauri's `Command`/`CommandEvent`
+// model: each line is emitted as `sidecar://stdout` / `sidecar://stderr`
+// and a terminal `sidecar://close` carries the exit code. Returns the run
+// id without waiting for the child to exit; output events may be emitted
+// before the command returns, so listen before invoking.
+#[tauri::command]
+fn run_sidecar_streaming(
+    app: AppHandle,
+    supervisor: State<Supervisor>,
+    window: Window,
+    input: String,
+) -> Result<u64, SidecarError> {
+    let sidecar_path = sidecar_path(&app)?;
+
+    if !verify_sidecar(&app, &sidecar_path) {
+        return Err(SidecarError::IntegrityFailed);
     }
 
-    let mut child = Command::new(sidecar_path)
+    // Count the streaming run against the global concurrency cap; the
+    // permit rides along to the reaper thread and is released when the
+    // child exits.
+    let permit = supervisor
+        .sem
+        .acquire_timeout(Duration::from_millis(PERMIT_ACQUIRE_TIMEOUT_MS))
+        .ok_or(SidecarError::Timeout)?;
+
+    let mut command = Command::new(&sidecar_path);
+    command
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
-        .spawn()
-        .map_err(|e| e.to_string())?;
+        .stderr(Stdio::piped());
+    let child = Arc::new(SharedChild::spawn(&mut command)?);
 
-    use std::io::Write;
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin
-            .write_all(input.as_bytes())
-            .map_err(|e| e.to_string())?;
+    let run_id = NEXT_RUN_ID.fetch_add(1, Ordering::Relaxed);
+
+    // Track the child by run id so `cancel_sidecar` can kill an in-flight
+    // streaming run, same as a one-shot run.
+    supervisor
+        .running
+        .lock()
+        .map_err(|e| SidecarError::Internal(e.to_string()))?
+        .insert(run_id.to_string(), child.clone());
+
+    // Feed stdin on its own thread so writing input can't deadlock against
+    // the child blocking on a full stdout pipe, and so the command returns
+    // the run id immediately rather than after the full write.
+    if let Some(mut stdin) = child.take_stdin() {
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        });
     }
 
-    let output = child
-        .wait_with_output()
-        .map_err(|e| e.to_string())?;
+    // Forward each pipe on its own thread so a slow reader on one stream
+    // can't stall the other.
+    let stdout_reader = child.take_stdout().map(|stdout| {
+        let window = window.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                let _ = window.emit("sidecar://stdout", (run_id, line));
+            }
+        })
+    });
+    let stderr_reader = child.take_stderr().map(|stderr| {
+        let window = window.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                let _ = window.emit("sidecar://stderr", (run_id, line));
+            }
+        })
+    });
+
+    // Reap the child from a helper thread so the reaper can enforce a
+    // timeout: a streaming sidecar that never exits is killed rather than
+    // leaking a process and a permit. On exit (or timeout) announce the
+    // code, untrack the run, and release the concurrency slot.
+    let running = supervisor.running.clone();
+    let waiter = child.clone();
+    std::thread::spawn(move || {
+        let (done_tx, done_rx) = mpsc::channel();
+        let wait_child = waiter.clone();
+        std::thread::spawn(move || {
+            let _ = done_tx.send(wait_child.wait());
+        });
+
+        let timeout = Duration::from_millis(DEFAULT_RUN_TIMEOUT_MS);
+        let code = match done_rx.recv_timeout(timeout) {
+            Ok(Ok(status)) => status.code().unwrap_or(-1),
+            Ok(Err(_)) => -1,
+            Err(_) => {
+                let _ = waiter.kill();
+                let _ = waiter.wait();
+                -1
+            }
+        };
+
+        // Let both readers drain so `sidecar://close` is the last event.
+        for reader in [stdout_reader, stderr_reader].into_iter().flatten() {
+            let _ = reader.join();
+        }
+
+        let _ = window.emit("sidecar://close", (run_id, code));
+        if let Ok(mut running) = running.lock() {
+            running.remove(&run_id.to_string());
+        }
+        drop(permit);
+    });
 
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    Ok(run_id)
+}
+
+// How long to wait for a freshly spawned session sidecar to connect back
+// before giving up, so a process that never dials the socket can't hang
+// the subsystem.
+const SESSION_CONNECT_TIMEOUT_MS: u64 = 10_000;
+
+// A warm sidecar: the spawned child plus the accepted local-socket
+// connection we talk to it over. Keeping the process alive between calls
+// avoids paying its startup cost (model load, DB open) on every request.
+// The permit keeps the session counted against the session pool's cap
+// (`MAX_CONCURRENT_SESSIONS`) for as long as it is open.
+struct Session {
+    child: Arc<SharedChild>,
+    stream: LocalSocketStream,
+    _permit: SemaphorePermit,
+}
+
+impl Session {
+    // Exchange one length-prefixed request for one length-prefixed
+    // response. Frames are a big-endian u32 byte count followed by the
+    // payload, matching the sidecar's side of the channel.
+    fn request(&mut self, input: &str) -> Result<String, SidecarError> {
+        let len = u32::try_from(input.len())
+            .map_err(|_| SidecarError::Session("request too large".to_string()))?;
+        self.stream.write_all(&len.to_be_bytes())?;
+        self.stream.write_all(input.as_bytes())?;
+        self.stream.flush()?;
+
+        let mut len_buf = [0u8; 4];
+        self.stream.read_exact(&mut len_buf)?;
+        let mut payload = vec![0u8; u32::from_be_bytes(len_buf) as usize];
+        self.stream.read_exact(&mut payload)?;
+        String::from_utf8(payload).map_err(|e| SidecarError::Session(e.to_string()))
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+// Live sidecar sessions keyed by the caller-supplied id. Each session sits
+// behind its own `Mutex` so a slow request on one can't block I/O on
+// another; the outer lock is only ever held briefly to look a session up.
+// Sessions have their own permit pool, separate from one-shot runs, because
+// each open session pins a permit for its whole lifetime.
+struct SessionStore {
+    sessions: Mutex<HashMap<String, Arc<Mutex<Session>>>>,
+    sem: Arc<Semaphore>,
+}
+
+impl Default for SessionStore {
+    fn default() -> Self {
+        SessionStore {
+            sessions: Mutex::new(HashMap::new()),
+            sem: Arc::new(Semaphore::new(MAX_CONCURRENT_SESSIONS)),
+        }
+    }
+}
+
+// Local socket name for a session: a named pipe on Windows, a filesystem
+// Unix domain socket elsewhere.
+fn socket_name(id: &str) -> String {
+    let pid = std::process::id();
+    if cfg!(windows) {
+        format!(r"\\.\pipe\axiom-hive-{pid}-{id}")
+    } else {
+        format!("/tmp/axiom-hive-{pid}-{id}.sock")
+    }
+}
+
+// Launch the verified sidecar once and establish a persistent channel to
+// it. The socket name is passed as the first argument so the sidecar knows
+// where to connect back. The store lock is only taken to check that the id
+// is free and to insert the finished session — spawning and the blocking accept
+// happen without it so a non-connecting child can't wedge the subsystem.
+#[tauri::command]
+fn sidecar_open(
+    app: AppHandle,
+    store: State<SessionStore>,
+    id: String,
+) -> Result<(), SidecarError> {
+    {
+        let sessions = store.sessions.lock().map_err(|e| SidecarError::Internal(e.to_string()))?;
+        if sessions.contains_key(&id) {
+            return Err(SidecarError::Session(format!("session {id} is already open")));
+        }
+    }
+
+    let sidecar_path = sidecar_path(&app)?;
+    if !verify_sidecar(&app, &sidecar_path) {
+        return Err(SidecarError::IntegrityFailed);
+    }
+
+    let permit = store
+        .sem
+        .acquire_timeout(Duration::from_millis(PERMIT_ACQUIRE_TIMEOUT_MS))
+        .ok_or(SidecarError::Timeout)?;
+
+    let name = socket_name(&id);
+    let listener = LocalSocketListener::bind(name.clone())
+        .map_err(|e| SidecarError::Session(e.to_string()))?;
+
+    let mut command = Command::new(&sidecar_path);
+    command.arg(&name);
+    let child = Arc::new(SharedChild::spawn(&mut command)?);
+
+    // Accept on a helper thread so the connect can be bounded; kill the
+    // child if it never dials in.
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(listener.accept());
+    });
+    let stream = match rx.recv_timeout(Duration::from_millis(SESSION_CONNECT_TIMEOUT_MS)) {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => {
+            let _ = child.kill();
+            return Err(SidecarError::Session(e.to_string()));
+        }
+        Err(_) => {
+            let _ = child.kill();
+            return Err(SidecarError::Session("sidecar did not connect in time".to_string()));
+        }
+    };
+
+    let session = Arc::new(Mutex::new(Session { child, stream, _permit: permit }));
+
+    let mut sessions = store.sessions.lock().map_err(|e| SidecarError::Internal(e.to_string()))?;
+    if sessions.contains_key(&id) {
+        // Lost a race with a concurrent open; drop ours (killing its child).
+        return Err(SidecarError::Session(format!("session {id} is already open")));
+    }
+    sessions.insert(id, session);
+    Ok(())
+}
+
+// Send one request to an open session and return its response. Only the
+// target session's lock is held during I/O; a watchdog kills the sidecar
+// if it doesn't reply within `timeout_ms` (default
+// `DEFAULT_RUN_TIMEOUT_MS`), unblocking the read instead of wedging.
+#[tauri::command]
+fn sidecar_send(
+    store: State<SessionStore>,
+    id: String,
+    input: String,
+    timeout_ms: Option<u64>,
+) -> Result<String, SidecarError> {
+    let session = {
+        let sessions = store.sessions.lock().map_err(|e| SidecarError::Internal(e.to_string()))?;
+        sessions
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| SidecarError::Session(format!("no open session {id}")))?
+    };
+
+    let mut guard = session.lock().map_err(|e| SidecarError::Internal(e.to_string()))?;
+
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_RUN_TIMEOUT_MS));
+    let killed = Arc::new(AtomicBool::new(false));
+    let (done_tx, done_rx) = mpsc::channel::<()>();
+    let watch_child = guard.child.clone();
+    let watch_killed = killed.clone();
+    let watchdog = std::thread::spawn(move || {
+        if done_rx.recv_timeout(timeout).is_err() {
+            watch_killed.store(true, Ordering::Relaxed);
+            let _ = watch_child.kill();
+        }
+    });
+
+    let result = guard.request(&input);
+    let _ = done_tx.send(());
+    let _ = watchdog.join();
+
+    // If the watchdog killed the child, or the exchange itself failed, the
+    // session's process/socket is no longer usable. Drop the guard and evict
+    // it so a later send reports "no open session" instead of a confusing
+    // I/O error against a dead corpse.
+    let timed_out = killed.load(Ordering::Relaxed);
+    if timed_out || result.is_err() {
+        drop(guard);
+        if let Ok(mut sessions) = store.sessions.lock() {
+            sessions.remove(&id);
+        }
+    }
+
+    if timed_out {
+        return Err(SidecarError::Timeout);
+    }
+    result
+}
+
+// Tear down a session, killing its process (handled by `Session`'s `Drop`).
+#[tauri::command]
+fn sidecar_close(store: State<SessionStore>, id: String) -> Result<(), SidecarError> {
+    let mut sessions = store.sessions.lock().map_err(|e| SidecarError::Internal(e.to_string()))?;
+    sessions
+        .remove(&id)
+        .map(|_| ())
+        .ok_or_else(|| SidecarError::Session(format!("no open session {id}")))
 }
 
 fn main() {
     tauri::Builder::default()
-        .invoke_handler(tauri::generate_handler![run_sidecar])
+        .manage(SessionStore::default())
+        .manage(Supervisor::default())
+        .invoke_handler(tauri::generate_handler![
+            run_sidecar,
+            run_sidecar_streaming,
+            cancel_sidecar,
+            sidecar_open,
+            sidecar_send,
+            sidecar_close
+        ])
         .run(tauri::generate_context!())
         .expect("error while running Axiom Hive");
 }